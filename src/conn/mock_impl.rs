@@ -9,13 +9,86 @@
 
 use std::hash::BuildHasherDefault as BldHshrDflt;
 use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::thread;
+use std::io;
+use std::rc::Rc;
 use fnv::FnvHasher;
 use {Params, Value, Column, FromValueError, from_value, from_value_opt};
 use super::{GenericConnection, GenericRow, GenericQueryResult, GenericStmt};
 use error::Result as MyResult;
+use error::{Error as MyError, MySqlError, DriverError};
 use prelude::*;
 use std::sync::Arc;
 
+/// Wraps a mock closure together with an optional call-count expectation.
+///
+/// Every call recorded against an `Expect` bumps an internal counter. If an
+/// expected count was set, exceeding it panics immediately rather than
+/// waiting for the end of the test; falling short of it is instead caught
+/// by `MockConnection`'s `Drop` implementation.
+struct Expect<F: ?Sized> {
+    name: &'static str,
+    expected: Option<usize>,
+    calls: Cell<usize>,
+    f: Box<F>,
+}
+
+impl<F: ?Sized> Expect<F> {
+    fn new(name: &'static str, f: Box<F>) -> Expect<F> {
+        Expect {
+            name: name,
+            expected: None,
+            calls: Cell::new(0),
+            f: f,
+        }
+    }
+
+    fn with_times(name: &'static str, expected: usize, f: Box<F>) -> Expect<F> {
+        Expect {
+            name: name,
+            expected: Some(expected),
+            calls: Cell::new(0),
+            f: f,
+        }
+    }
+
+    /// Records a call against this expectation, panicking if it would push
+    /// the call count past the configured expectation.
+    fn record_call(&self) {
+        let calls = self.calls.get() + 1;
+        if let Some(expected) = self.expected {
+            if calls > expected {
+                panic!(
+                    "{} called too many times: expected {}, got {}",
+                    self.name,
+                    expected,
+                    calls
+                );
+            }
+        }
+        self.calls.set(calls);
+    }
+
+    /// Checks the expectation was met exactly, for use when the mock is
+    /// being dropped at the end of a test.
+    fn check(&self) {
+        if let Some(expected) = self.expected {
+            let calls = self.calls.get();
+            if calls != expected {
+                panic!(
+                    "{} not called enough times: expected {}, got {}",
+                    self.name,
+                    expected,
+                    calls
+                );
+            }
+        }
+    }
+}
+
 /// A struct representing a type of request for a value from a row - i.e. get or take. These are
 /// stored inside a MockRow, for developers to query and write tests based on the value.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -109,12 +182,107 @@ impl GenericRow for MockRow {
 }
 
 pub struct MockStmt {
-    pub params: Option<Vec<Column>>,
-    pub columns: Option<Vec<Column>>,
-    pub column_indexes: HashMap<String, usize, BldHshrDflt<FnvHasher>>,
-    pub fn_execute: Option<Box<Fn(&str, Params) -> MyResult<MockQueryResult>>>,
-    pub fn_first_exec: Option<Box<Fn(&str, Params) -> MyResult<Option<MockRow>>>>,
-    pub query: String,
+    params: Option<Vec<Column>>,
+    columns: Option<Vec<Column>>,
+    column_indexes: HashMap<String, usize, BldHshrDflt<FnvHasher>>,
+    fn_execute: Option<Expect<Fn(usize, &str, Params) -> MyResult<MockQueryResult>>>,
+    fn_first_exec: Option<Expect<Fn(usize, &str, Params) -> MyResult<Option<MockRow>>>>,
+    query: String,
+
+    /// Queue of results returned by successive `execute()` calls. Takes
+    /// priority over `fn_execute` once `append_execute_result` has been
+    /// called at least once.
+    execute_queue: VecDeque<MyResult<MockQueryResult>>,
+    execute_queue_enabled: bool,
+
+    /// Shared log this statement funnels executed statements into. Set by
+    /// `MockConnection::prepare` so `execute`/`first_exec` are captured in
+    /// the same transaction log as the owning connection's own methods.
+    log: Option<Rc<RefCell<MockLog>>>,
+}
+
+impl MockStmt {
+    pub fn new<Q: Into<String>>(query: Q) -> MockStmt {
+        MockStmt {
+            params: None,
+            columns: None,
+            column_indexes: HashMap::default(),
+            fn_execute: None,
+            fn_first_exec: None,
+            query: query.into(),
+            execute_queue: VecDeque::new(),
+            execute_queue_enabled: false,
+            log: None,
+        }
+    }
+
+    pub fn with_params(mut self, params: Vec<Column>) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    pub fn with_columns(mut self, columns: Vec<Column>) -> Self {
+        self.columns = Some(columns);
+        self
+    }
+
+    pub fn with_column_indexes(mut self, column_indexes: HashMap<String, usize, BldHshrDflt<FnvHasher>>) -> Self {
+        self.column_indexes = column_indexes;
+        self
+    }
+
+    /// Appends a result to the end of the `execute()` result queue. Once this
+    /// has been called, `execute()` returns queued results in order instead
+    /// of calling `fn_execute`, panicking if the queue runs dry before the
+    /// calls do.
+    pub fn append_execute_result(mut self, result: MyResult<MockQueryResult>) -> Self {
+        self.execute_queue_enabled = true;
+        self.execute_queue.push_back(result);
+        self
+    }
+
+    /// Shortcut for `self.append_execute_result(Err(error))`: the next call
+    /// to `execute()` fails with `error`. Chain further
+    /// `append_execute_result` calls to script later attempts succeeding.
+    pub fn with_execute_error(self, error: MyError) -> Self {
+        self.append_execute_result(Err(error))
+    }
+
+    pub fn with_fn_execute<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize, &str, Params) -> MyResult<MockQueryResult> + 'static,
+    {
+        self.fn_execute = Some(Expect::new("execute", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_execute`, but additionally asserts on drop that
+    /// `execute()` was called exactly `expected` times.
+    pub fn with_fn_execute_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize, &str, Params) -> MyResult<MockQueryResult> + 'static,
+    {
+        self.fn_execute = Some(Expect::with_times("execute", expected, Box::new(f)));
+        self
+    }
+
+    pub fn with_fn_first_exec<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize, &str, Params) -> MyResult<Option<MockRow>> + 'static,
+    {
+        self.fn_first_exec = Some(Expect::new("first_exec", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_first_exec`, but additionally asserts on drop that
+    /// `first_exec()` was called exactly `expected` times.
+    pub fn with_fn_first_exec_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize, &str, Params) -> MyResult<Option<MockRow>> + 'static,
+    {
+        self.fn_first_exec = Some(Expect::with_times("first_exec", expected, Box::new(f)));
+        self
+    }
 }
 
 impl<'a> GenericStmt<'a> for MockStmt {
@@ -127,15 +295,65 @@ impl<'a> GenericStmt<'a> for MockStmt {
         self.column_indexes.get(&name.as_ref().to_owned()).cloned()
     }
     fn execute<T: Into<Params>>(&'a mut self, params: T) -> MyResult<Self::QueryResult> {
-        if self.fn_execute.is_some() { self.fn_execute.as_mut().unwrap()(&self.query, params.into()) }
-        else { 
-            panic!("Tried to call execute() on a mock statement without and implementation") 
+        let params = params.into();
+        if let Some(ref log) = self.log {
+            let mut log = log.borrow_mut();
+            log.record_call(RecordedCall::StmtExecute {
+                sql: self.query.clone(),
+                params: params.clone(),
+            });
+            log.log_statement(&self.query, params.clone());
+        }
+        if self.execute_queue_enabled {
+            if let Some(ref e) = self.fn_execute {
+                e.record_call();
+            }
+            return self.execute_queue.pop_front().unwrap_or_else(|| {
+                panic!("execute result queue exhausted: execute() was called more times than results were appended")
+            });
+        }
+        if let Some(ref e) = self.fn_execute {
+            let idx = e.calls.get();
+            e.record_call();
+            (e.f)(idx, &self.query, params)
+        } else {
+            panic!("Tried to call execute() on a mock statement without an implementation")
         }
     }
     fn first_exec<T: Into<Params>>(&'a mut self, params: T) -> MyResult<Option<Self::Row>> {
-        if self.fn_first_exec.is_some() { self.fn_first_exec.as_mut().unwrap()(&self.query, params.into()) }
-        else { 
-            panic!("Tried to call execute() on a mock statement without and implementation") 
+        let params = params.into();
+        if let Some(ref log) = self.log {
+            let mut log = log.borrow_mut();
+            log.record_call(RecordedCall::StmtFirstExec {
+                sql: self.query.clone(),
+                params: params.clone(),
+            });
+            log.log_statement(&self.query, params.clone());
+        }
+        if let Some(ref e) = self.fn_first_exec {
+            let idx = e.calls.get();
+            e.record_call();
+            (e.f)(idx, &self.query, params)
+        } else {
+            panic!("Tried to call first_exec() on a mock statement without an implementation")
+        }
+    }
+}
+
+impl Drop for MockStmt {
+    /// Asserts that every call-count expectation set on this statement was met.
+    ///
+    /// Skipped while the thread is already unwinding from another panic, so a
+    /// failing assertion elsewhere in a test doesn't get masked by an abort.
+    fn drop(&mut self) {
+        if thread::panicking() {
+            return;
+        }
+        if let Some(ref e) = self.fn_execute {
+            e.check();
+        }
+        if let Some(ref e) = self.fn_first_exec {
+            e.check();
         }
     }
 }
@@ -172,6 +390,101 @@ impl GenericQueryResult  for MockQueryResult {
     fn more_results_exists(&self) -> bool { self.more_results_exists }
 }
 
+/// A single statement recorded as part of a logged transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoggedStatement {
+    pub query: String,
+    pub params: Params,
+}
+
+/// A completed transaction recorded by `MockConnection`: the statements that
+/// ran inside it, in order, and whether it was committed or rolled back.
+///
+/// Statements run outside an explicit `begin`/`commit`/`rollback` pair are
+/// recorded as their own single-statement transaction with `committed: true`,
+/// so every call a unit under test makes shows up here uniformly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoggedTransaction {
+    pub statements: Vec<LoggedStatement>,
+    pub committed: bool,
+}
+
+/// One call made to a `MockConnection` method, or to a `MockStmt` it
+/// prepared (`StmtExecute`/`StmtFirstExec`), captured automatically on
+/// every invocation regardless of whether a mock closure was installed for it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordedCall {
+    Query { sql: String },
+    First { sql: String },
+    Prepare { sql: String },
+    PrepExec { sql: String, params: Params },
+    FirstExec { sql: String, params: Params },
+    StmtExecute { sql: String, params: Params },
+    StmtFirstExec { sql: String, params: Params },
+}
+
+/// Recording state shared between a `MockConnection` and every `MockStmt` it
+/// prepares, so statements run through a prepared statement's `execute`/
+/// `first_exec` are captured in the same transaction log and call history as
+/// statements run directly through the connection.
+struct MockLog {
+    current_transaction: Option<Vec<LoggedStatement>>,
+    transaction_log: Vec<LoggedTransaction>,
+    recorded_calls: Vec<RecordedCall>,
+}
+
+impl MockLog {
+    fn new() -> MockLog {
+        MockLog {
+            current_transaction: None,
+            transaction_log: Vec::new(),
+            recorded_calls: Vec::new(),
+        }
+    }
+
+    fn record_call(&mut self, call: RecordedCall) {
+        self.recorded_calls.push(call);
+    }
+
+    /// Records a statement against the currently open transaction, or as its
+    /// own implicit, single-statement, committed transaction if none is open.
+    fn log_statement(&mut self, query: &str, params: Params) {
+        let statement = LoggedStatement {
+            query: query.to_owned(),
+            params: params,
+        };
+        match self.current_transaction {
+            Some(ref mut statements) => statements.push(statement),
+            None => {
+                self.transaction_log.push(LoggedTransaction {
+                    statements: vec![statement],
+                    committed: true,
+                });
+            }
+        }
+    }
+
+    fn begin(&mut self) {
+        self.current_transaction = Some(Vec::new());
+    }
+
+    /// No-op if no transaction is open (e.g. an errant `commit()`/`rollback()`
+    /// with no preceding `begin()`), so it doesn't pollute the transaction log
+    /// with a phantom, empty-statement transaction nobody asked for.
+    fn close_transaction(&mut self, committed: bool) {
+        if let Some(statements) = self.current_transaction.take() {
+            self.transaction_log.push(LoggedTransaction {
+                statements: statements,
+                committed: committed,
+            });
+        }
+    }
+
+    fn drain_transaction_log(&mut self) -> Vec<LoggedTransaction> {
+        ::std::mem::replace(&mut self.transaction_log, Vec::new())
+    }
+}
+
 /// Mock implementation for a DB connection.
 ///
 /// # Important
@@ -181,22 +494,434 @@ impl GenericQueryResult  for MockQueryResult {
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// // Initialise a mock connection with a mock prepare() method, then pass it
 /// // into the register() function.
 /// let mock_connection = MockConnection::new()
-///     .with_fn_prepare(|q| {
-///         log!("Query = {}", q);
-///
+///     .with_fn_prepare(|idx, q| {
+///         println!("prepare() call #{}: {}", idx, q);
+///         Ok(MockStmt::new(q))
 ///     });
 /// ```
 #[allow(dead_code)]
 pub struct MockConnection {
-    pub fn_query: Option<Box<Fn(&str) -> MyResult<MockQueryResult>>>,
-    pub fn_first: Option<Box<Fn(&str) -> MyResult<Option<MockRow>>>>,
-    pub fn_prepare: Option<Box<Fn(&str) -> MyResult<MockStmt>>>,
-    pub fn_prep_exec: Option<Box<Fn(&str, Params) -> MyResult<MockQueryResult>>>,
-    pub fn_first_exec: Option<Box<Fn(&str, Params) -> MyResult<Option<MockRow>>>>,
+    fn_query: Option<Expect<Fn(usize, &str) -> MyResult<MockQueryResult>>>,
+    fn_first: Option<Expect<Fn(usize, &str) -> MyResult<Option<MockRow>>>>,
+    fn_prepare: Option<Expect<Fn(usize, &str) -> MyResult<MockStmt>>>,
+    fn_prep_exec: Option<Expect<Fn(usize, &str, Params) -> MyResult<MockQueryResult>>>,
+    fn_first_exec: Option<Expect<Fn(usize, &str, Params) -> MyResult<Option<MockRow>>>>,
+
+    /// Queue of results returned by successive `query()` calls. Takes priority
+    /// over `fn_query` once `append_query_result` has been called at least once.
+    query_queue: VecDeque<MyResult<MockQueryResult>>,
+    query_queue_enabled: bool,
+    /// Queue of results returned by successive `prep_exec()` calls. Takes priority
+    /// over `fn_prep_exec` once `append_prep_exec_result` has been called at least once.
+    prep_exec_queue: VecDeque<MyResult<MockQueryResult>>,
+    prep_exec_queue_enabled: bool,
+
+    fn_begin: Option<Expect<Fn(usize) -> MyResult<()>>>,
+    fn_commit: Option<Expect<Fn(usize) -> MyResult<()>>>,
+    fn_rollback: Option<Expect<Fn(usize) -> MyResult<()>>>,
+
+    /// Transaction log and call history, shared with every `MockStmt` this
+    /// connection prepares so `execute`/`first_exec` on a prepared statement
+    /// are captured uniformly alongside the connection's own methods.
+    log: Rc<RefCell<MockLog>>,
+}
+
+impl MockConnection {
+    pub fn new() -> MockConnection {
+        MockConnection {
+            fn_query: None,
+            fn_first: None,
+            fn_prepare: None,
+            fn_prep_exec: None,
+            fn_first_exec: None,
+            fn_begin: None,
+            fn_commit: None,
+            fn_rollback: None,
+            log: Rc::new(RefCell::new(MockLog::new())),
+            query_queue: VecDeque::new(),
+            query_queue_enabled: false,
+            prep_exec_queue: VecDeque::new(),
+            prep_exec_queue_enabled: false,
+        }
+    }
+
+    /// Appends a result to the end of the `query()` result queue. Once this has
+    /// been called, `query()` returns queued results in order instead of calling
+    /// `fn_query`, panicking if the queue runs dry before the calls do.
+    pub fn append_query_result(mut self, result: MyResult<MockQueryResult>) -> Self {
+        self.query_queue_enabled = true;
+        self.query_queue.push_back(result);
+        self
+    }
+
+    /// Appends a result to the end of the `prep_exec()` result queue. Once this
+    /// has been called, `prep_exec()` returns queued results in order instead of
+    /// calling `fn_prep_exec`, panicking if the queue runs dry before the calls do.
+    pub fn append_prep_exec_result(mut self, result: MyResult<MockQueryResult>) -> Self {
+        self.prep_exec_queue_enabled = true;
+        self.prep_exec_queue.push_back(result);
+        self
+    }
+
+    /// Shortcut for `self.append_query_result(Err(error))`: the next call to
+    /// `query()` fails with `error`. Chain further `append_query_result` calls
+    /// to script later attempts succeeding.
+    pub fn with_query_error(self, error: MyError) -> Self {
+        self.append_query_result(Err(error))
+    }
+
+    /// Shortcut for `self.append_prep_exec_result(Err(error))`: the next call
+    /// to `prep_exec()` fails with `error`. Chain further
+    /// `append_prep_exec_result` calls to script later attempts succeeding.
+    pub fn with_prep_exec_error(self, error: MyError) -> Self {
+        self.append_prep_exec_result(Err(error))
+    }
+
+    pub fn with_fn_query<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize, &str) -> MyResult<MockQueryResult> + 'static,
+    {
+        self.fn_query = Some(Expect::new("query", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_query`, but additionally asserts on drop that `query()` was
+    /// called exactly `expected` times.
+    pub fn with_fn_query_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize, &str) -> MyResult<MockQueryResult> + 'static,
+    {
+        self.fn_query = Some(Expect::with_times("query", expected, Box::new(f)));
+        self
+    }
+
+    pub fn with_fn_first<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize, &str) -> MyResult<Option<MockRow>> + 'static,
+    {
+        self.fn_first = Some(Expect::new("first", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_first`, but additionally asserts on drop that `first()` was
+    /// called exactly `expected` times.
+    pub fn with_fn_first_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize, &str) -> MyResult<Option<MockRow>> + 'static,
+    {
+        self.fn_first = Some(Expect::with_times("first", expected, Box::new(f)));
+        self
+    }
+
+    pub fn with_fn_prepare<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize, &str) -> MyResult<MockStmt> + 'static,
+    {
+        self.fn_prepare = Some(Expect::new("prepare", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_prepare`, but additionally asserts on drop that `prepare()` was
+    /// called exactly `expected` times.
+    pub fn with_fn_prepare_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize, &str) -> MyResult<MockStmt> + 'static,
+    {
+        self.fn_prepare = Some(Expect::with_times("prepare", expected, Box::new(f)));
+        self
+    }
+
+    pub fn with_fn_prep_exec<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize, &str, Params) -> MyResult<MockQueryResult> + 'static,
+    {
+        self.fn_prep_exec = Some(Expect::new("prep_exec", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_prep_exec`, but additionally asserts on drop that `prep_exec()`
+    /// was called exactly `expected` times.
+    pub fn with_fn_prep_exec_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize, &str, Params) -> MyResult<MockQueryResult> + 'static,
+    {
+        self.fn_prep_exec = Some(Expect::with_times("prep_exec", expected, Box::new(f)));
+        self
+    }
+
+    pub fn with_fn_first_exec<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize, &str, Params) -> MyResult<Option<MockRow>> + 'static,
+    {
+        self.fn_first_exec = Some(Expect::new("first_exec", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_first_exec`, but additionally asserts on drop that
+    /// `first_exec()` was called exactly `expected` times.
+    pub fn with_fn_first_exec_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize, &str, Params) -> MyResult<Option<MockRow>> + 'static,
+    {
+        self.fn_first_exec = Some(Expect::with_times("first_exec", expected, Box::new(f)));
+        self
+    }
+
+    pub fn with_fn_begin<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize) -> MyResult<()> + 'static,
+    {
+        self.fn_begin = Some(Expect::new("begin", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_begin`, but additionally asserts on drop that `begin()` was
+    /// called exactly `expected` times.
+    pub fn with_fn_begin_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize) -> MyResult<()> + 'static,
+    {
+        self.fn_begin = Some(Expect::with_times("begin", expected, Box::new(f)));
+        self
+    }
+
+    pub fn with_fn_commit<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize) -> MyResult<()> + 'static,
+    {
+        self.fn_commit = Some(Expect::new("commit", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_commit`, but additionally asserts on drop that `commit()` was
+    /// called exactly `expected` times.
+    pub fn with_fn_commit_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize) -> MyResult<()> + 'static,
+    {
+        self.fn_commit = Some(Expect::with_times("commit", expected, Box::new(f)));
+        self
+    }
+
+    pub fn with_fn_rollback<F>(mut self, f: F) -> Self
+    where
+        F: Fn(usize) -> MyResult<()> + 'static,
+    {
+        self.fn_rollback = Some(Expect::new("rollback", Box::new(f)));
+        self
+    }
+
+    /// Like `with_fn_rollback`, but additionally asserts on drop that `rollback()`
+    /// was called exactly `expected` times.
+    pub fn with_fn_rollback_times<F>(mut self, expected: usize, f: F) -> Self
+    where
+        F: Fn(usize) -> MyResult<()> + 'static,
+    {
+        self.fn_rollback = Some(Expect::with_times("rollback", expected, Box::new(f)));
+        self
+    }
+
+    /// Opens a mock transaction. Statements run through `query`/`first`/
+    /// `prep_exec`/`first_exec` until the matching `commit`/`rollback` are
+    /// grouped together in the transaction log.
+    ///
+    /// Defaults to `Ok(())` if no `fn_begin` hook was installed.
+    pub fn begin(&mut self) -> MyResult<()> {
+        let result = match self.fn_begin {
+            Some(ref e) => {
+                let idx = e.calls.get();
+                e.record_call();
+                (e.f)(idx)
+            }
+            None => Ok(()),
+        };
+        if result.is_ok() {
+            self.log.borrow_mut().begin();
+        }
+        result
+    }
+
+    /// Closes the open mock transaction, recording it in the transaction log
+    /// as committed.
+    ///
+    /// Defaults to `Ok(())` if no `fn_commit` hook was installed.
+    pub fn commit(&mut self) -> MyResult<()> {
+        let result = match self.fn_commit {
+            Some(ref e) => {
+                let idx = e.calls.get();
+                e.record_call();
+                (e.f)(idx)
+            }
+            None => Ok(()),
+        };
+        if result.is_ok() {
+            self.log.borrow_mut().close_transaction(true);
+        }
+        result
+    }
+
+    /// Closes the open mock transaction, recording it in the transaction log
+    /// as rolled back.
+    ///
+    /// Defaults to `Ok(())` if no `fn_rollback` hook was installed.
+    pub fn rollback(&mut self) -> MyResult<()> {
+        let result = match self.fn_rollback {
+            Some(ref e) => {
+                let idx = e.calls.get();
+                e.record_call();
+                (e.f)(idx)
+            }
+            None => Ok(()),
+        };
+        if result.is_ok() {
+            self.log.borrow_mut().close_transaction(false);
+        }
+        result
+    }
+
+    /// Drains and returns every transaction logged so far, leaving the log empty.
+    pub fn drain_transaction_log(&mut self) -> Vec<LoggedTransaction> {
+        self.log.borrow_mut().drain_transaction_log()
+    }
+
+    /// Every call made to this connection, and to the statements it prepared,
+    /// so far, in order.
+    pub fn recorded_calls(&self) -> Vec<RecordedCall> {
+        self.log.borrow().recorded_calls.clone()
+    }
+
+    /// The most recent call made to this connection, if any.
+    pub fn last_call(&self) -> Option<RecordedCall> {
+        self.log.borrow().recorded_calls.last().cloned()
+    }
+
+    pub fn calls_to_query(&self) -> Vec<RecordedCall> {
+        self.log.borrow()
+            .recorded_calls
+            .iter()
+            .filter(|c| match **c {
+                RecordedCall::Query { .. } => true,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn calls_to_first(&self) -> Vec<RecordedCall> {
+        self.log.borrow()
+            .recorded_calls
+            .iter()
+            .filter(|c| match **c {
+                RecordedCall::First { .. } => true,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn calls_to_prepare(&self) -> Vec<RecordedCall> {
+        self.log.borrow()
+            .recorded_calls
+            .iter()
+            .filter(|c| match **c {
+                RecordedCall::Prepare { .. } => true,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn calls_to_prep_exec(&self) -> Vec<RecordedCall> {
+        self.log.borrow()
+            .recorded_calls
+            .iter()
+            .filter(|c| match **c {
+                RecordedCall::PrepExec { .. } => true,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn calls_to_first_exec(&self) -> Vec<RecordedCall> {
+        self.log.borrow()
+            .recorded_calls
+            .iter()
+            .filter(|c| match **c {
+                RecordedCall::FirstExec { .. } => true,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Every call made to a `MockStmt` prepared by this connection via
+    /// `execute()`, in order.
+    pub fn calls_to_stmt_execute(&self) -> Vec<RecordedCall> {
+        self.log.borrow()
+            .recorded_calls
+            .iter()
+            .filter(|c| match **c {
+                RecordedCall::StmtExecute { .. } => true,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Every call made to a `MockStmt` prepared by this connection via
+    /// `first_exec()`, in order.
+    pub fn calls_to_stmt_first_exec(&self) -> Vec<RecordedCall> {
+        self.log.borrow()
+            .recorded_calls
+            .iter()
+            .filter(|c| match **c {
+                RecordedCall::StmtFirstExec { .. } => true,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+impl Drop for MockConnection {
+    /// Asserts that every call-count expectation set on this connection was met.
+    ///
+    /// Skipped while the thread is already unwinding from another panic, so a
+    /// failing assertion elsewhere in a test doesn't get masked by an abort.
+    fn drop(&mut self) {
+        if thread::panicking() {
+            return;
+        }
+        if let Some(ref e) = self.fn_query {
+            e.check();
+        }
+        if let Some(ref e) = self.fn_first {
+            e.check();
+        }
+        if let Some(ref e) = self.fn_prepare {
+            e.check();
+        }
+        if let Some(ref e) = self.fn_prep_exec {
+            e.check();
+        }
+        if let Some(ref e) = self.fn_first_exec {
+            e.check();
+        }
+        if let Some(ref e) = self.fn_begin {
+            e.check();
+        }
+        if let Some(ref e) = self.fn_commit {
+            e.check();
+        }
+        if let Some(ref e) = self.fn_rollback {
+            e.check();
+        }
+    }
 }
 
 impl<'a> GenericConnection<'a> for MockConnection {
@@ -205,27 +930,56 @@ impl<'a> GenericConnection<'a> for MockConnection {
     type Row = MockRow;
 
     fn query<Q: AsRef<str>>(&mut self, query: Q) -> MyResult<Self::QueryResult> {
-        if self.fn_query.is_some() {
-            self.fn_query.as_ref().unwrap()(query.as_ref())
+        {
+            let mut log = self.log.borrow_mut();
+            log.record_call(RecordedCall::Query { sql: query.as_ref().to_owned() });
+            log.log_statement(query.as_ref(), Params::Empty);
+        }
+        if self.query_queue_enabled {
+            if let Some(ref e) = self.fn_query {
+                e.record_call();
+            }
+            return self.query_queue.pop_front().unwrap_or_else(|| {
+                panic!("query result queue exhausted: query() was called more times than results were appended")
+            });
+        }
+        if let Some(ref e) = self.fn_query {
+            let idx = e.calls.get();
+            e.record_call();
+            (e.f)(idx, query.as_ref())
         } else {
             panic!("Tried to call query() on mock connection with no implementation");
         }
     }
 
     fn first<Q: AsRef<str>>(&mut self, query: Q) -> MyResult<Option<Self::Row>> {
-        if self.fn_first.is_some() {
-            self.fn_first.as_ref().unwrap()(query.as_ref())
+        {
+            let mut log = self.log.borrow_mut();
+            log.record_call(RecordedCall::First { sql: query.as_ref().to_owned() });
+            log.log_statement(query.as_ref(), Params::Empty);
+        }
+        if let Some(ref e) = self.fn_first {
+            let idx = e.calls.get();
+            e.record_call();
+            (e.f)(idx, query.as_ref())
         } else {
             panic!("Tried to call first() on mock connection with no implementation");
         }
     }
 
     fn prepare<Q: AsRef<str>>(&mut self, query: Q) -> MyResult<Self::Stmt> {
-        if self.fn_prepare.is_some() {
-            self.fn_prepare.as_ref().unwrap()(query.as_ref())
+        self.log.borrow_mut().record_call(RecordedCall::Prepare { sql: query.as_ref().to_owned() });
+        let stmt = if let Some(ref e) = self.fn_prepare {
+            let idx = e.calls.get();
+            e.record_call();
+            (e.f)(idx, query.as_ref())
         } else {
             panic!("Tried to call prepare() on mock connection with no implementation");
-        }
+        };
+        stmt.map(|mut stmt| {
+            stmt.log = Some(self.log.clone());
+            stmt
+        })
     }
 
     fn prep_exec<Q, P>(&mut self, query: Q, params: P) -> MyResult<Self::QueryResult>
@@ -233,8 +987,27 @@ impl<'a> GenericConnection<'a> for MockConnection {
         Q: AsRef<str>,
         P: Into<Params>,
     {
-        if self.fn_prep_exec.is_some() {
-            self.fn_prep_exec.as_ref().unwrap()(query.as_ref(), params.into())
+        let params = params.into();
+        {
+            let mut log = self.log.borrow_mut();
+            log.record_call(RecordedCall::PrepExec {
+                sql: query.as_ref().to_owned(),
+                params: params.clone(),
+            });
+            log.log_statement(query.as_ref(), params.clone());
+        }
+        if self.prep_exec_queue_enabled {
+            if let Some(ref e) = self.fn_prep_exec {
+                e.record_call();
+            }
+            return self.prep_exec_queue.pop_front().unwrap_or_else(|| {
+                panic!("prep_exec result queue exhausted: prep_exec() was called more times than results were appended")
+            });
+        }
+        if let Some(ref e) = self.fn_prep_exec {
+            let idx = e.calls.get();
+            e.record_call();
+            (e.f)(idx, query.as_ref(), params)
         } else {
             panic!("Tried to call prep_exec() on mock connection with no implementation");
         }
@@ -245,10 +1018,316 @@ impl<'a> GenericConnection<'a> for MockConnection {
         Q: AsRef<str>,
         P: Into<Params>,
     {
-        if self.fn_first_exec.is_some() {
-            self.fn_first_exec.as_ref().unwrap()(query.as_ref(), params.into())
+        let params = params.into();
+        {
+            let mut log = self.log.borrow_mut();
+            log.record_call(RecordedCall::FirstExec {
+                sql: query.as_ref().to_owned(),
+                params: params.clone(),
+            });
+            log.log_statement(query.as_ref(), params.clone());
+        }
+        if let Some(ref e) = self.fn_first_exec {
+            let idx = e.calls.get();
+            e.record_call();
+            (e.f)(idx, query.as_ref(), params)
         } else {
             panic!("Tried to call first_exec() on mock connection with no implementation");
         }
     }
 }
+
+thread_local! {
+    /// The current thread's installed default mock connection, if any. Set by
+    /// `MockConnection::install`, cleared by `MockGuard`, and read through
+    /// `with_current_mock`; no connection-acquiring path consults it itself.
+    static CURRENT_MOCK: RefCell<Option<MockConnection>> = RefCell::new(None);
+}
+
+/// RAII guard returned by `MockConnection::install`. Clears this thread's
+/// installed mock connection when dropped.
+pub struct MockGuard {
+    _private: (),
+}
+
+impl Drop for MockGuard {
+    fn drop(&mut self) {
+        CURRENT_MOCK.with(|cell| {
+            *cell.borrow_mut() = None;
+        });
+    }
+}
+
+impl MockConnection {
+    /// Installs `self` as this thread's default mock connection, returning a
+    /// guard that clears it again when dropped.
+    ///
+    /// Mock data is stored per-thread, so parallel tests don't interfere with
+    /// one another. A thread spawned while a mock is installed does not
+    /// inherit it; re-install from within the spawned thread if it also
+    /// needs one.
+    pub fn install(self) -> MockGuard {
+        CURRENT_MOCK.with(|cell| {
+            *cell.borrow_mut() = Some(self);
+        });
+        MockGuard { _private: () }
+    }
+}
+
+/// Runs `f` with mutable access to the current thread's installed mock
+/// connection, if one has been installed via `MockConnection::install`.
+///
+/// Returns `None` without calling `f` if no mock is installed on this thread.
+pub fn with_current_mock<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce(&mut MockConnection) -> R,
+{
+    CURRENT_MOCK.with(|cell| cell.borrow_mut().as_mut().map(f))
+}
+
+/// Constructors for realistic `error::Error` values, for scripting error
+/// injection in mock connections without hand-rolling the crate's error
+/// variants in every test.
+pub struct MockError;
+
+impl MockError {
+    /// A server error as it would arrive over the wire: an error code, a
+    /// SQLSTATE, and a human-readable message.
+    pub fn server_error<S: Into<String>, M: Into<String>>(code: u16, state: S, message: M) -> MyError {
+        MyError::MySqlError(MySqlError {
+            code: code,
+            state: state.into(),
+            message: message.into(),
+        })
+    }
+
+    /// MySQL error 1213 / SQLSTATE 40001, "Deadlock found when trying to get
+    /// lock; try restarting transaction" -- the canonical error for
+    /// exercising transaction-retry logic.
+    pub fn deadlock_error() -> MyError {
+        MockError::server_error(
+            1213,
+            "40001",
+            "Deadlock found when trying to get lock; try restarting transaction",
+        )
+    }
+
+    /// A driver-level error, for simulating failures below the protocol
+    /// layer (e.g. a connection that could not be established).
+    pub fn driver_error(message: &str) -> MyError {
+        MyError::DriverError(DriverError::CouldNotConnect(Some(message.to_owned())))
+    }
+
+    /// An IO error of the given kind, for simulating a severed connection.
+    pub fn io_error(kind: io::ErrorKind, message: &str) -> MyError {
+        MyError::IoError(io::Error::new(kind, message.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dummy_query_result() -> MockQueryResult {
+        MockQueryResult {
+            affected_rows: 0,
+            last_insert_id: 0,
+            warnings: 0,
+            info: Vec::new(),
+            column_indexes: HashMap::default(),
+            columns: Vec::new(),
+            more_results_exists: false,
+            rows: None,
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "query not called enough times: expected 1, got 0")]
+    fn unmet_expectation_panics_on_drop() {
+        let _conn = MockConnection::new().with_fn_query_times(1, |_, _| Ok(dummy_query_result()));
+    }
+
+    #[test]
+    fn met_expectation_does_not_panic() {
+        let mut conn = MockConnection::new().with_fn_query_times(1, |_, _| Ok(dummy_query_result()));
+        conn.query("SELECT 1").unwrap();
+    }
+
+    #[test]
+    fn panicking_drop_does_not_mask_original_panic() {
+        let result = thread::spawn(|| {
+            let _conn = MockConnection::new().with_fn_query_times(1, |_, _| Ok(dummy_query_result()));
+            panic!("boom");
+        }).join();
+
+        let err = result.expect_err("thread should have panicked");
+        let msg = err.downcast_ref::<&str>().cloned().unwrap_or("");
+        assert_eq!(msg, "boom");
+    }
+
+    #[test]
+    #[should_panic(expected = "query result queue exhausted: query() was called more times than results were appended")]
+    fn query_queue_exhaustion_panics() {
+        let mut conn = MockConnection::new().append_query_result(Ok(dummy_query_result()));
+        conn.query("SELECT 1").unwrap();
+        let _ = conn.query("SELECT 2");
+    }
+
+    #[test]
+    fn transaction_log_groups_explicit_and_implicit_statements() {
+        let mut conn = MockConnection::new().with_fn_query(|_, _| Ok(dummy_query_result()));
+
+        conn.query("implicit one").unwrap();
+        conn.begin().unwrap();
+        conn.query("in transaction").unwrap();
+        conn.commit().unwrap();
+        conn.query("implicit two").unwrap();
+
+        let log = conn.drain_transaction_log();
+        assert_eq!(log.len(), 3);
+
+        assert!(log[0].committed);
+        assert_eq!(log[0].statements.len(), 1);
+        assert_eq!(log[0].statements[0].query, "implicit one");
+
+        assert!(log[1].committed);
+        assert_eq!(log[1].statements.len(), 1);
+        assert_eq!(log[1].statements[0].query, "in transaction");
+
+        assert!(log[2].committed);
+        assert_eq!(log[2].statements.len(), 1);
+        assert_eq!(log[2].statements[0].query, "implicit two");
+
+        // Draining empties the log.
+        assert!(conn.drain_transaction_log().is_empty());
+    }
+
+    #[test]
+    fn calls_to_accessors_return_the_right_subset_in_order() {
+        let mut conn = MockConnection::new()
+            .with_fn_query(|_, _| Ok(dummy_query_result()))
+            .with_fn_prep_exec(|_, _, _| Ok(dummy_query_result()))
+            .with_fn_first_exec(|_, _, _| Ok(None));
+
+        conn.query("select a").unwrap();
+        conn.prep_exec("insert a", Params::Empty).unwrap();
+        conn.query("select b").unwrap();
+        conn.first_exec("select c", Params::Empty).unwrap();
+        conn.prep_exec("insert b", Params::Empty).unwrap();
+
+        let queries = conn.calls_to_query();
+        assert_eq!(queries.len(), 2);
+        match queries[0] {
+            RecordedCall::Query { ref sql } => assert_eq!(sql, "select a"),
+            ref other => panic!("unexpected call: {:?}", other),
+        }
+        match queries[1] {
+            RecordedCall::Query { ref sql } => assert_eq!(sql, "select b"),
+            ref other => panic!("unexpected call: {:?}", other),
+        }
+
+        let prep_execs = conn.calls_to_prep_exec();
+        assert_eq!(prep_execs.len(), 2);
+        match prep_execs[0] {
+            RecordedCall::PrepExec { ref sql, .. } => assert_eq!(sql, "insert a"),
+            ref other => panic!("unexpected call: {:?}", other),
+        }
+        match prep_execs[1] {
+            RecordedCall::PrepExec { ref sql, .. } => assert_eq!(sql, "insert b"),
+            ref other => panic!("unexpected call: {:?}", other),
+        }
+
+        let first_execs = conn.calls_to_first_exec();
+        assert_eq!(first_execs.len(), 1);
+        match first_execs[0] {
+            RecordedCall::FirstExec { ref sql, .. } => assert_eq!(sql, "select c"),
+            ref other => panic!("unexpected call: {:?}", other),
+        }
+
+        // calls_to_* variants that weren't exercised come back empty, not
+        // accidentally matching an unrelated call.
+        assert!(conn.calls_to_first().is_empty());
+        assert!(conn.calls_to_prepare().is_empty());
+
+        match conn.last_call() {
+            Some(RecordedCall::PrepExec { ref sql, .. }) => assert_eq!(sql, "insert b"),
+            other => panic!("unexpected last call: {:?}", other),
+        }
+
+        assert_eq!(conn.recorded_calls().len(), 5);
+    }
+
+    #[test]
+    fn install_and_guard_drop_manage_the_thread_local_mock() {
+        assert!(with_current_mock(|_| ()).is_none());
+
+        let guard = MockConnection::new()
+            .with_fn_query_times(1, |_, _| Ok(dummy_query_result()))
+            .install();
+
+        let result = with_current_mock(|conn| conn.query("SELECT 1"));
+        assert!(result.is_some());
+        assert!(result.unwrap().is_ok());
+
+        drop(guard);
+
+        assert!(with_current_mock(|_| ()).is_none());
+    }
+
+    #[test]
+    fn with_query_error_surfaces_the_injected_error() {
+        let mut conn = MockConnection::new().with_query_error(MockError::deadlock_error());
+
+        match conn.query("SELECT 1") {
+            Err(MyError::MySqlError(ref e)) => {
+                assert_eq!(e.code, 1213);
+                assert_eq!(e.state, "40001");
+                assert_eq!(
+                    e.message,
+                    "Deadlock found when trying to get lock; try restarting transaction"
+                );
+            }
+            Err(_) => panic!("expected a deadlock MySqlError"),
+            Ok(_) => panic!("expected query() to surface the injected error"),
+        }
+    }
+
+    #[test]
+    fn mock_error_constructors_build_the_intended_variants() {
+        match MockError::server_error(1205, "HY000", format!("Lock wait timeout after {}s", 50)) {
+            MyError::MySqlError(e) => {
+                assert_eq!(e.code, 1205);
+                assert_eq!(e.state, "HY000");
+                assert_eq!(e.message, "Lock wait timeout after 50s");
+            }
+            _ => panic!("expected a MySqlError"),
+        }
+
+        match MockError::driver_error("could not connect") {
+            MyError::DriverError(DriverError::CouldNotConnect(Some(ref msg))) => {
+                assert_eq!(msg, "could not connect")
+            }
+            _ => panic!("expected a DriverError::CouldNotConnect"),
+        }
+
+        match MockError::io_error(io::ErrorKind::ConnectionReset, "connection reset by peer") {
+            MyError::IoError(ref e) => assert_eq!(e.kind(), io::ErrorKind::ConnectionReset),
+            _ => panic!("expected an IoError"),
+        }
+    }
+
+    #[test]
+    fn commit_or_rollback_without_begin_does_not_log_a_phantom_transaction() {
+        let mut conn = MockConnection::new().with_fn_query(|_, _| Ok(dummy_query_result()));
+
+        conn.query("implicit").unwrap();
+        // Errant rollback with no preceding begin() - should be a no-op.
+        conn.rollback().unwrap();
+        conn.commit().unwrap();
+
+        let log = conn.drain_transaction_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].statements.len(), 1);
+        assert_eq!(log[0].statements[0].query, "implicit");
+    }
+}